@@ -0,0 +1,38 @@
+#![feature(phase)]
+
+extern crate serialize;
+extern crate cargo;
+extern crate docopt;
+#[phase(plugin)] extern crate docopt_macros;
+
+use cargo::ops;
+use cargo::execute_main_without_stdin;
+use cargo::core::MultiShell;
+use cargo::util::CliResult;
+use cargo::util::important_paths::find_root_manifest_for_cwd;
+
+docopt!(Options, "
+Read a value out of the merged Cargo configuration
+
+Usage:
+    cargo-config get [options] <key>
+
+Options:
+    -h, --help              Print this message
+    --manifest-path PATH    Path to the manifest to infer the config root from
+    -v, --verbose           Use verbose output
+",  flag_manifest_path: Option<String>, arg_key: String)
+
+fn main() {
+    execute_main_without_stdin(execute, true);
+}
+
+fn execute(options: Options, shell: &mut MultiShell) -> CliResult<Option<()>> {
+    let root = try!(find_root_manifest_for_cwd(options.flag_manifest_path));
+    shell.set_verbose(options.flag_verbose);
+
+    let value = try!(ops::get_config(root.dir_path(), options.arg_key.as_slice()));
+    println!("{}", value);
+
+    Ok(None)
+}
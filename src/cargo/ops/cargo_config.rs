@@ -0,0 +1,28 @@
+use util::config;
+use util::config::ConfigValue;
+use util::CargoResult;
+
+/// Resolve a dotted key (e.g. `build.jobs`) so that `cargo config get`
+/// reports the exact same value and provenance as the rest of Cargo --
+/// this just forwards to `util::config::get_config`, the one resolver for
+/// dotted keys against the merged, environment-spliced config tree.
+pub fn get_config(pwd: Path, key: &str) -> CargoResult<ConfigValue> {
+    config::get_config(pwd, key)
+}
+
+#[cfg(test)]
+mod test {
+    use std::io;
+    use util::config::{mod, Project, Integer};
+    use super::get_config;
+
+    #[test]
+    fn forwards_to_util_config_get_config() {
+        let dir = io::TempDir::new("cargo-config-test").unwrap();
+        config::set_config(dir.path().clone(), Project, "build.jobs",
+                            Integer(2)).unwrap();
+
+        let value = get_config(dir.path().clone(), "build.jobs").unwrap();
+        assert_eq!(value.integer().unwrap(), 2);
+    }
+}
@@ -0,0 +1,3 @@
+pub use self::cargo_config::get_config;
+
+mod cargo_config;
@@ -19,20 +19,44 @@ pub struct Config<'a> {
 impl<'a> Config<'a> {
     pub fn new<'a>(shell: &'a mut MultiShell,
                    jobs: Option<uint>,
-                   target: Option<String>) -> CargoResult<Config<'a>> {
+                   target: Option<String>,
+                   pwd: Path) -> CargoResult<Config<'a>> {
         if jobs == Some(0) {
             return Err(human("jobs must be at least 1"))
         }
+
+        // `.cargo/config`'s `[build]` table supplies defaults for whichever
+        // of these the caller didn't pass on the command line; CLI flags
+        // always win.
+        let build = get_config(pwd, "build").ok();
+        let build = build.as_ref().and_then(|cfg| cfg.table().ok());
+
+        // Only consult (and validate) build.jobs when the caller didn't pass
+        // --jobs; a stale or invalid config value shouldn't block a command
+        // that already has a valid value from the CLI.
+        let cfg_jobs = if jobs.is_none() {
+            match build.and_then(|b| config_int(b, "jobs")) {
+                Some(i) if i <= 0 => return Err(human("build.jobs must be at least 1")),
+                Some(i) => Some(i as uint),
+                None => None,
+            }
+        } else {
+            None
+        };
+        let cfg_target = build.and_then(|b| config_string(b, "target"));
+        let cfg_linker = build.and_then(|b| config_string(b, "linker"));
+        let cfg_ar = build.and_then(|b| config_string(b, "ar"));
+
         Ok(Config {
             home_path: try!(os::homedir().require(|| {
                 human("Cargo couldn't find your home directory. \
                       This probably means that $HOME was not set.")
             })),
             shell: shell,
-            jobs: jobs.unwrap_or(os::num_cpus()),
-            target: target,
-            ar: None,
-            linker: None,
+            jobs: jobs.or(cfg_jobs).unwrap_or(os::num_cpus()),
+            target: target.or(cfg_target),
+            ar: cfg_ar,
+            linker: cfg_linker,
         })
     }
 
@@ -81,6 +105,8 @@ pub enum ConfigValueValue {
     String(String),
     List(Vec<String>),
     Table(HashMap<String, ConfigValue>),
+    Integer(i64),
+    Boolean(bool),
 }
 
 impl fmt::Show for ConfigValueValue {
@@ -89,6 +115,8 @@ impl fmt::Show for ConfigValueValue {
             String(ref string) => write!(f, "{}", string),
             List(ref list) => write!(f, "{}", list),
             Table(ref table) => write!(f, "{}", table),
+            Integer(i) => write!(f, "{}", i),
+            Boolean(b) => write!(f, "{}", b),
         }
     }
 }
@@ -99,6 +127,8 @@ impl<E, S: Encoder<E>> Encodable<S, E> for ConfigValueValue {
             String(ref string) => string.encode(s),
             List(ref list) => list.encode(s),
             Table(ref table) => table.encode(s),
+            Integer(i) => i.encode(s),
+            Boolean(b) => b.encode(s),
         }
     }
 }
@@ -121,6 +151,8 @@ impl ConfigValue {
     fn from_toml(path: &Path, toml: toml::Value) -> CargoResult<ConfigValue> {
         let value = match toml {
             toml::String(val) => String(val),
+            toml::Integer(val) => Integer(val),
+            toml::Boolean(val) => Boolean(val),
             toml::Array(val) => {
                 List(try!(result::collect(val.move_iter().map(|toml| {
                     match toml {
@@ -148,6 +180,14 @@ impl ConfigValue {
                 mem::swap(old, new);
                 self.path = path;
             }
+            (&Integer(ref mut old), Integer(new)) => {
+                *old = new;
+                self.path = path;
+            }
+            (&Boolean(ref mut old), Boolean(new)) => {
+                *old = new;
+                self.path = path;
+            }
             (&List(ref mut old), List(ref mut new)) => {
                 old.extend(mem::replace(new, Vec::new()).move_iter());
                 self.path.extend(path.move_iter());
@@ -174,35 +214,76 @@ impl ConfigValue {
 
     pub fn string(&self) -> CargoResult<&str> {
         match self.value {
-            Table(_) => Err(internal("expected a string, but found a table")),
-            List(_) => Err(internal("expected a string, but found a list")),
             String(ref s) => Ok(s.as_slice()),
+            ref other => self.mismatch("a string", other),
         }
     }
 
     pub fn table(&self) -> CargoResult<&HashMap<String, ConfigValue>> {
         match self.value {
-            String(_) => Err(internal("expected a table, but found a string")),
-            List(_) => Err(internal("expected a table, but found a list")),
             Table(ref table) => Ok(table),
+            ref other => self.mismatch("a table", other),
         }
     }
 
     pub fn list(&self) -> CargoResult<&[String]> {
         match self.value {
-            String(_) => Err(internal("expected a list, but found a string")),
-            Table(_) => Err(internal("expected a list, but found a table")),
             List(ref list) => Ok(list.as_slice()),
+            ref other => self.mismatch("a list", other),
+        }
+    }
+
+    pub fn integer(&self) -> CargoResult<i64> {
+        match self.value {
+            Integer(i) => Ok(i),
+            ref other => self.mismatch("an integer", other),
         }
     }
+
+    pub fn boolean(&self) -> CargoResult<bool> {
+        match self.value {
+            Boolean(b) => Ok(b),
+            ref other => self.mismatch("a boolean", other),
+        }
+    }
+
+    // Type-mismatch errors are `human` errors that carry the value's
+    // provenance (every value tracks where it came from), so a mismatch in a
+    // merged multi-file config points at the offending file instead of
+    // leaving the user to guess.
+    fn mismatch<T>(&self, expected: &str, found: &ConfigValueValue) -> CargoResult<T> {
+        let paths: Vec<String> = self.path.iter().map(|p| {
+            p.display().to_string()
+        }).collect();
+        Err(human(format!("expected {}, but found {} (defined in {})",
+                          expected, found.desc(), paths)))
+    }
 }
 
 impl ConfigValueValue {
     fn desc(&self) -> &'static str {
         match *self {
-            Table(..) => "table",
-            List(..) => "array",
-            String(..) => "string",
+            Table(..) => "a table",
+            List(..) => "a list",
+            String(..) => "a string",
+            Integer(..) => "an integer",
+            Boolean(..) => "a boolean",
+        }
+    }
+
+    fn to_toml(&self) -> toml::Value {
+        match *self {
+            String(ref s) => toml::String(s.clone()),
+            Integer(i) => toml::Integer(i),
+            Boolean(b) => toml::Boolean(b),
+            List(ref list) => {
+                toml::Array(list.iter().map(|s| toml::String(s.clone())).collect())
+            }
+            Table(ref table) => {
+                toml::Table(table.iter().map(|(k, v)| {
+                    (k.clone(), v.get_value().to_toml())
+                }).collect())
+            }
         }
     }
 }
@@ -226,9 +307,36 @@ impl fmt::Show for ConfigValue {
     }
 }
 
+fn config_string(table: &HashMap<String, ConfigValue>, key: &str) -> Option<String> {
+    table.find(&key.to_string()).and_then(|v| v.string().ok()).map(|s| s.to_string())
+}
+
+fn config_int(table: &HashMap<String, ConfigValue>, key: &str) -> Option<i64> {
+    table.find(&key.to_string()).and_then(|v| v.integer().ok())
+}
+
+// This is the one resolver for a dotted config key: it walks the fully
+// merged tree (every ancestor `.cargo/config`, with environment overrides
+// already spliced in by `all_configs`) so that `Config::new`'s single-key
+// lookups and `ops::cargo_config::get_config`'s `cargo config get` report
+// the exact same value and provenance.
 pub fn get_config(pwd: Path, key: &str) -> CargoResult<ConfigValue> {
-    find_in_tree(&pwd, |file| extract_config(file, key)).map_err(|_|
-        human(format!("`{}` not found in your configuration", key)))
+    let all = try!(all_configs(pwd));
+    let mut parts = key.split('.');
+    let head = parts.next().unwrap_or(key);
+
+    let mut value = try!(all.find(&head.to_string()).require(|| {
+        human(format!("`{}` not found in your configuration", key))
+    })).clone();
+
+    for part in parts {
+        let table = try!(value.table()).clone();
+        value = try!(table.find(&part.to_string()).require(|| {
+            human(format!("`{}` not found in your configuration", key))
+        })).clone();
+    }
+
+    Ok(value)
 }
 
 pub fn all_configs(pwd: Path) -> CargoResult<HashMap<String, ConfigValue>> {
@@ -246,6 +354,7 @@ pub fn all_configs(pwd: Path) -> CargoResult<HashMap<String, ConfigValue>> {
         Ok(())
     }).map_err(|_| human("Couldn't load Cargo configuration")));
 
+    try!(merge_environment(&mut cfg));
 
     match cfg.value {
         Table(map) => Ok(map),
@@ -253,54 +362,230 @@ pub fn all_configs(pwd: Path) -> CargoResult<HashMap<String, ConfigValue>> {
     }
 }
 
-fn find_in_tree<T>(pwd: &Path,
-                   walk: |io::fs::File| -> CargoResult<T>) -> CargoResult<T> {
-    let mut current = pwd.clone();
-
-    loop {
-        let possible = current.join(".cargo").join("config");
-        if possible.exists() {
-            let file = try!(io::fs::File::open(&possible));
+// `os::getenv` only ever hands back a `String`, but the key it's overriding
+// might be an `Integer` or `Boolean` (e.g. `build.jobs`). Each entry in
+// `ENV_CONFIG_KEYS` carries its own expected kind so the override is parsed
+// correctly even when no file already declares the key -- that's the whole
+// point of overriding config in CI without editing files.
+enum EnvKeyKind {
+    EnvString,
+    EnvInteger,
+    EnvBoolean,
+}
 
-            match walk(file) {
-                Ok(res) => return Ok(res),
-                _ => ()
+// Only config keys Cargo itself understands are eligible for an environment
+// override -- folding every `CARGO_`-prefixed var (`CARGO_HOME`,
+// `CARGO_PKG_*`, the ones Cargo sets for build scripts, ...) into the
+// config tree as a top-level key would be wrong.
+static ENV_CONFIG_KEYS: &'static [(&'static str, EnvKeyKind)] = &[
+    ("BUILD_JOBS", EnvInteger),
+    ("BUILD_TARGET", EnvString),
+    ("BUILD_LINKER", EnvString),
+    ("BUILD_AR", EnvString),
+    ("TERM_VERBOSE", EnvBoolean),
+];
+
+fn coerce_scalar(kind: &EnvKeyKind, val: String, var: &str) -> CargoResult<ConfigValueValue> {
+    match *kind {
+        EnvInteger => {
+            from_str::<i64>(val.as_slice()).map(Integer).require(|| {
+                human(format!("`{}` is not a valid integer for {}", val, var))
+            })
+        }
+        EnvBoolean => {
+            match val.as_slice() {
+                "true" => Ok(Boolean(true)),
+                "false" => Ok(Boolean(false)),
+                _ => Err(human(format!("`{}` is not a valid boolean for {}", val, var))),
             }
         }
+        EnvString => Ok(String(val)),
+    }
+}
 
-        if !current.pop() { break; }
+fn merge_environment(cfg: &mut ConfigValue) -> CargoResult<()> {
+    for &(suffix, ref kind) in ENV_CONFIG_KEYS.iter() {
+        let var = format!("CARGO_{}", suffix);
+        let val = match os::getenv(var.as_slice()) {
+            Some(val) => val,
+            None => continue,
+        };
+
+        let key = suffix.to_ascii_lower();
+        let parts: Vec<&str> = key.as_slice().split('_').collect();
+        let path = vec![Path::new(format!("environment variable {}", var))];
+        let value = try!(coerce_scalar(kind, val, var.as_slice()));
+
+        try!(cfg.merge(env_nested_value(parts.as_slice(), value, &path)));
+    }
+    Ok(())
+}
+
+fn env_nested_value(parts: &[&str], val: ConfigValueValue, path: &Vec<Path>) -> ConfigValue {
+    if parts.len() <= 1 {
+        ConfigValue { value: val, path: path.clone() }
+    } else {
+        let mut table = HashMap::new();
+        table.insert(parts[0].to_string(),
+                     env_nested_value(parts.slice_from(1), val, path));
+        ConfigValue { value: Table(table), path: path.clone() }
     }
+}
+
+pub fn set_config(pwd: Path, location: Location, key: &str,
+                   value: ConfigValueValue) -> CargoResult<()> {
+    let file_path = match location {
+        Project => pwd.join(".cargo").join("config"),
+        Global => {
+            try!(os::homedir().require(|| {
+                human("Cargo couldn't find your home directory. \
+                      This probably means that $HOME was not set.")
+            })).join(".cargo").join("config")
+        }
+    };
+
+    try!(io::fs::mkdir_recursive(&file_path.dir_path(), io::UserRWX));
+
+    let mut toml = if file_path.exists() {
+        let mut file = try!(io::fs::File::open(&file_path));
+        let contents = try!(file.read_to_string());
+        try!(cargo_toml::parse(contents.as_slice(), &file_path).chain_error(|| {
+            internal(format!("could not parse Toml manifest; path={}",
+                             file_path.display()))
+        }))
+    } else {
+        HashMap::new()
+    };
+
+    try!(insert_dotted(&mut toml, key, value.to_toml()));
 
-    Err(internal(""))
+    let mut file = try!(io::fs::File::create(&file_path));
+    try!(file.write_str(toml::Table(toml).to_string().as_slice()));
+
+    Ok(())
+}
+
+fn insert_dotted(table: &mut HashMap<String, toml::Value>, key: &str,
+                  value: toml::Value) -> CargoResult<()> {
+    match key.find('.') {
+        Some(idx) => {
+            let head = key.slice_to(idx).to_string();
+            let tail = key.slice_from(idx + 1);
+            let mut sub = match table.pop(&head) {
+                Some(toml::Table(t)) => t,
+                Some(_) => {
+                    return Err(human(format!(
+                        "failed to set `{}`: `{}` is already set to a non-table value",
+                        key, head)))
+                }
+                None => HashMap::new(),
+            };
+            try!(insert_dotted(&mut sub, tail, value));
+            table.insert(head, toml::Table(sub));
+        }
+        None => { table.insert(key.to_string(), value); }
+    }
+    Ok(())
 }
 
+// `ConfigValue::merge` lets a later call win over an earlier one, so the
+// order files are fed to `walk` here is the config's precedence order,
+// lowest-to-highest. Collect ancestor `.cargo/config` files from `pwd` up to
+// the filesystem root, then walk them starting from the *farthest*
+// ancestor so the closest, most project-specific file is merged last and
+// wins.
 fn walk_tree(pwd: &Path,
              walk: |io::fs::File| -> CargoResult<()>) -> CargoResult<()> {
+    let mut possible_configs = Vec::new();
     let mut current = pwd.clone();
-    let mut err = false;
 
     loop {
         let possible = current.join(".cargo").join("config");
         if possible.exists() {
-            let file = try!(io::fs::File::open(&possible));
-
-            match walk(file) {
-                Err(_) => err = false,
-                _ => ()
-            }
+            possible_configs.push(possible);
         }
-
-        if err { return Err(internal("")); }
         if !current.pop() { break; }
     }
 
+    let mut err = false;
+    for possible in possible_configs.iter().rev() {
+        let file = try!(io::fs::File::open(possible));
+
+        match walk(file) {
+            Err(_) => err = false,
+            _ => ()
+        }
+    }
+
+    if err { return Err(internal("")); }
+
     Ok(())
 }
 
-fn extract_config(mut file: io::fs::File, key: &str) -> CargoResult<ConfigValue> {
-    let contents = try!(file.read_to_string());
-    let mut toml = try!(cargo_toml::parse(contents.as_slice(), file.path()));
-    let val = try!(toml.pop(&key.to_string()).require(|| internal("")));
+#[cfg(test)]
+mod test {
+    use std::io;
+    use std::os;
+    use toml;
+    use super::{all_configs, ConfigValue};
+
+    #[test]
+    fn env_override_coerces_to_the_keys_own_type_with_no_file() {
+        let dir = io::TempDir::new("cargo-config-test").unwrap();
+        os::setenv("CARGO_BUILD_JOBS", "4");
+
+        let configs = all_configs(dir.path().clone());
+
+        os::unsetenv("CARGO_BUILD_JOBS");
+
+        let build = configs.unwrap().find(&"build".to_string()).unwrap()
+                            .table().unwrap().clone();
+        assert_eq!(build.find(&"jobs".to_string()).unwrap().integer().unwrap(), 4);
+    }
+
+    #[test]
+    fn project_local_config_wins_over_ancestor_config() {
+        let root = io::TempDir::new("cargo-config-test").unwrap();
+        let child = root.path().join("child");
 
-    ConfigValue::from_toml(file.path(), val)
+        io::fs::mkdir_recursive(&root.path().join(".cargo"), io::UserRWX).unwrap();
+        io::fs::mkdir_recursive(&child.join(".cargo"), io::UserRWX).unwrap();
+
+        io::fs::File::create(&root.path().join(".cargo").join("config")).unwrap()
+                     .write_str("[build]\n    jobs = 1\n").unwrap();
+        io::fs::File::create(&child.join(".cargo").join("config")).unwrap()
+                     .write_str("[build]\n    jobs = 2\n").unwrap();
+
+        let configs = all_configs(child);
+
+        let build = configs.unwrap().find(&"build".to_string()).unwrap()
+                            .table().unwrap().clone();
+        assert_eq!(build.find(&"jobs".to_string()).unwrap().integer().unwrap(), 2);
+    }
+
+    #[test]
+    fn integer_and_boolean_from_toml_merge_and_accessors() {
+        let path = Path::new("/test/.cargo/config");
+        let mut jobs = ConfigValue::from_toml(&path, toml::Integer(1)).unwrap();
+        let verbose = ConfigValue::from_toml(&path, toml::Boolean(true)).unwrap();
+
+        assert_eq!(jobs.integer().unwrap(), 1);
+        assert_eq!(verbose.boolean().unwrap(), true);
+        assert!(jobs.boolean().is_err());
+
+        let newer_jobs = ConfigValue::from_toml(&path, toml::Integer(4)).unwrap();
+        jobs.merge(newer_jobs).unwrap();
+        assert_eq!(jobs.integer().unwrap(), 4);
+    }
+
+    #[test]
+    fn set_config_round_trips_through_get_config() {
+        let dir = io::TempDir::new("cargo-config-test").unwrap();
+
+        super::set_config(dir.path().clone(), super::Project, "build.jobs",
+                           super::Integer(3)).unwrap();
+
+        let value = super::get_config(dir.path().clone(), "build.jobs").unwrap();
+        assert_eq!(value.integer().unwrap(), 3);
+    }
 }